@@ -1,14 +1,32 @@
 use std::env;
 
+/// Default local-dev rustfs endpoint. Paired with `DEFAULT_DEV_ACCESS_KEY` /
+/// `DEFAULT_DEV_SECRET_KEY`, which the credential provider chain falls back
+/// to only when this default endpoint is in effect, so the out-of-the-box
+/// config stays internally consistent without baking dev secrets into a
+/// real deployment's resolution chain.
+pub const DEFAULT_S3_ENDPOINT: &str = "http://rustfs:9000";
+pub const DEFAULT_DEV_ACCESS_KEY: &str = "rustfsadmin";
+pub const DEFAULT_DEV_SECRET_KEY: &str = "rustfsadmin";
+
 #[derive(Clone)]
 pub struct Config {
     pub port: u16,
     pub s3_endpoint: String,
-    pub s3_access_key: String,
-    pub s3_secret_key: String,
+    /// Explicit static credentials. `None` when unset, so the S3 client's
+    /// credential provider chain can fall through to the next provider.
+    pub s3_access_key: Option<String>,
+    pub s3_secret_key: Option<String>,
     pub s3_region: String,
     pub webp_default_quality: u8,
     pub max_dimension: u32,
+    pub allowed_dimensions: Vec<u32>,
+    pub cache_bucket: Option<String>,
+    /// Source objects at or above this size are downloaded as concurrent
+    /// ranged parts instead of a single sequential `get_object` call.
+    pub parallel_download_threshold: u64,
+    pub parallel_download_part_size: u64,
+    pub parallel_download_max_concurrency: usize,
 }
 
 impl Config {
@@ -19,11 +37,9 @@ impl Config {
                 .parse()
                 .expect("CONVERTER_PORT must be a valid port number"),
             s3_endpoint: env::var("S3_ENDPOINT")
-                .unwrap_or_else(|_| "http://rustfs:9000".to_string()),
-            s3_access_key: env::var("S3_ACCESS_KEY")
-                .unwrap_or_else(|_| "rustfsadmin".to_string()),
-            s3_secret_key: env::var("S3_SECRET_KEY")
-                .unwrap_or_else(|_| "rustfsadmin".to_string()),
+                .unwrap_or_else(|_| DEFAULT_S3_ENDPOINT.to_string()),
+            s3_access_key: env::var("S3_ACCESS_KEY").ok(),
+            s3_secret_key: env::var("S3_SECRET_KEY").ok(),
             s3_region: env::var("S3_REGION")
                 .unwrap_or_else(|_| "us-east-1".to_string()),
             webp_default_quality: env::var("WEBP_DEFAULT_QUALITY")
@@ -34,6 +50,26 @@ impl Config {
                 .unwrap_or_else(|_| "4096".to_string())
                 .parse()
                 .expect("MAX_DIMENSION must be a valid number"),
+            allowed_dimensions: env::var("ALLOWED_DIMENSIONS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse().expect("ALLOWED_DIMENSIONS must be a comma-separated list of numbers"))
+                .collect(),
+            cache_bucket: env::var("CACHE_BUCKET").ok().filter(|s| !s.is_empty()),
+            parallel_download_threshold: env::var("PARALLEL_DOWNLOAD_THRESHOLD")
+                .unwrap_or_else(|_| "33554432".to_string())
+                .parse()
+                .expect("PARALLEL_DOWNLOAD_THRESHOLD must be a valid number"),
+            parallel_download_part_size: env::var("PARALLEL_DOWNLOAD_PART_SIZE")
+                .unwrap_or_else(|_| "8388608".to_string())
+                .parse()
+                .expect("PARALLEL_DOWNLOAD_PART_SIZE must be a valid number"),
+            parallel_download_max_concurrency: env::var("PARALLEL_DOWNLOAD_MAX_CONCURRENCY")
+                .unwrap_or_else(|_| "4".to_string())
+                .parse()
+                .expect("PARALLEL_DOWNLOAD_MAX_CONCURRENCY must be a valid number"),
         }
     }
 }
@@ -6,9 +6,15 @@ use axum::{
 use serde::Deserialize;
 
 use crate::error::ConverterError;
-use crate::services::converter::{convert, ConversionRequest, FitMode, OutputFormat};
+use crate::services::converter::{cache_key, convert, variant_etag, ConversionRequest, FitMode, OutputFormat};
 use crate::AppState;
 
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+/// Metadata key the source object's ETag is stashed under on cached variants,
+/// so a cache hit can recompute the same `variant_etag` the convert path
+/// would have produced without re-fetching the source object.
+const SOURCE_ETAG_METADATA_KEY: &str = "source-etag";
+
 #[derive(Debug, Deserialize)]
 pub struct ImageQuery {
     pub w: Option<u32>,
@@ -58,6 +64,9 @@ pub async fn get_image(
         if w == 0 {
             return Err(ConverterError::InvalidParameter("width must be greater than 0".to_string()));
         }
+        if !state.config.allowed_dimensions.is_empty() && !state.config.allowed_dimensions.contains(&w) {
+            return Err(ConverterError::InvalidParameter(format!("width {} is not in the allowed dimensions list", w)));
+        }
     }
     if let Some(h) = params.h {
         if h > state.config.max_dimension {
@@ -66,6 +75,9 @@ pub async fn get_image(
         if h == 0 {
             return Err(ConverterError::InvalidParameter("height must be greater than 0".to_string()));
         }
+        if !state.config.allowed_dimensions.is_empty() && !state.config.allowed_dimensions.contains(&h) {
+            return Err(ConverterError::InvalidParameter(format!("height {} is not in the allowed dimensions list", h)));
+        }
     }
 
     // Validate quality
@@ -84,9 +96,59 @@ pub async fn get_image(
     // Determine output format from Accept header
     let output_format = determine_format(&headers);
 
+    // If a cache bucket is configured, check it for an already-converted variant
+    let variant_key = state
+        .config
+        .cache_bucket
+        .as_ref()
+        .map(|_| cache_key(key, params.w, params.h, quality, fit_mode, output_format));
+
+    if let (Some(cache_bucket), Some(variant_key)) = (&state.config.cache_bucket, &variant_key) {
+        match state.s3_client.get_object_uncond(cache_bucket, variant_key).await {
+            Ok(cached) => {
+                tracing::debug!("Cache hit for variant: {}/{}", cache_bucket, variant_key);
+                // Recompute the same ETag the convert path would produce, from the
+                // source ETag stashed as metadata on the cached variant, so both
+                // paths agree and If-None-Match revalidation actually works.
+                let etag = cached
+                    .metadata
+                    .get(SOURCE_ETAG_METADATA_KEY)
+                    .map(|source_etag| {
+                        variant_etag(Some(source_etag), params.w, params.h, quality, fit_mode, output_format)
+                    })
+                    .unwrap_or_else(|| cached.etag.clone().unwrap_or_else(|| format!("\"{}\"", variant_key)));
+
+                if if_none_match_matches(&headers, &etag) {
+                    return Ok(not_modified_response(&etag));
+                }
+
+                return Ok(success_response(
+                    output_format.content_type(),
+                    &etag,
+                    cached.last_modified.as_deref(),
+                    cached.data,
+                ));
+            }
+            Err(ConverterError::NotFound(_)) => {
+                tracing::debug!("Cache miss for variant: {}/{}", cache_bucket, variant_key);
+            }
+            Err(e) => {
+                tracing::warn!("Cache lookup failed, falling back to conversion: {}", e);
+            }
+        }
+    }
+
     // Fetch image from S3
     tracing::debug!("Fetching image from S3: bucket={}, key={}", bucket, key);
-    let data = state.s3_client.get_object(bucket, key).await?;
+    let source = state.s3_client.get_object(bucket, key).await?;
+
+    // Compute a strong ETag from the source object's ETag plus the normalized
+    // transform parameters, so it changes when either one does
+    let etag = variant_etag(source.etag.as_deref(), params.w, params.h, quality, fit_mode, output_format);
+
+    if if_none_match_matches(&headers, &etag) {
+        return Ok(not_modified_response(&etag));
+    }
 
     // Convert image
     tracing::debug!(
@@ -98,7 +160,7 @@ pub async fn get_image(
     );
 
     let result = convert(ConversionRequest {
-        data,
+        data: source.data,
         width: params.w,
         height: params.h,
         output_format,
@@ -114,28 +176,126 @@ pub async fn get_image(
         result.output_height
     );
 
-    // Build response with headers
-    Ok((
-        StatusCode::OK,
+    // Populate the cache for subsequent requests, best-effort. Stash the
+    // source ETag as metadata so a future cache hit can reconstruct this
+    // same variant_etag.
+    if let (Some(cache_bucket), Some(variant_key)) = (&state.config.cache_bucket, &variant_key) {
+        let source_etag = source.etag.as_deref().unwrap_or("unknown");
+        let metadata = [(SOURCE_ETAG_METADATA_KEY, source_etag)];
+        if let Err(e) = state.s3_client.put_object(cache_bucket, variant_key, &result.data, &metadata).await {
+            tracing::warn!("Failed to write cached variant {}/{}: {}", cache_bucket, variant_key, e);
+        }
+    }
+
+    Ok(success_response(
+        result.content_type,
+        &etag,
+        source.last_modified.as_deref(),
+        result.data,
+    ))
+}
+
+/// Returns true when the request's `If-None-Match` header contains `etag` or `*`.
+fn if_none_match_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|value| value.split(',').any(|candidate| {
+            let candidate = candidate.trim();
+            candidate == "*" || candidate == etag
+        }))
+        .unwrap_or(false)
+}
+
+fn not_modified_response(etag: &str) -> Response {
+    (
+        StatusCode::NOT_MODIFIED,
         [
-            (header::CONTENT_TYPE, result.content_type),
-            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
-            (header::VARY, "Accept"),
+            (header::ETAG, etag.to_string()),
+            (header::CACHE_CONTROL, IMMUTABLE_CACHE_CONTROL.to_string()),
+            (header::VARY, "Accept".to_string()),
         ],
-        result.data,
     )
-        .into_response())
+        .into_response()
+}
+
+fn success_response(
+    content_type: &'static str,
+    etag: &str,
+    last_modified: Option<&str>,
+    data: bytes::Bytes,
+) -> Response {
+    let mut response_headers = vec![
+        (header::CONTENT_TYPE, content_type.to_string()),
+        (header::CACHE_CONTROL, IMMUTABLE_CACHE_CONTROL.to_string()),
+        (header::VARY, "Accept".to_string()),
+        (header::ETAG, etag.to_string()),
+    ];
+    if let Some(last_modified) = last_modified {
+        response_headers.push((header::LAST_MODIFIED, last_modified.to_string()));
+    }
+
+    (StatusCode::OK, response_headers, data).into_response()
 }
 
+/// Parse an `Accept` header value into `(mime, q)` pairs.
+/// Entries without an explicit `q` parameter default to `q=1.0`.
+fn parse_accept(accept: &str) -> Vec<(&str, f32)> {
+    accept
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let mime = parts.next()?.trim();
+            if mime.is_empty() {
+                return None;
+            }
+
+            let q = parts
+                .filter_map(|param| {
+                    let param = param.trim();
+                    param.strip_prefix("q=").and_then(|v| v.parse::<f32>().ok())
+                })
+                .next()
+                .unwrap_or(1.0);
+
+            Some((mime, q))
+        })
+        .collect()
+}
+
+/// Picks the best output format via real Accept-header quality negotiation,
+/// preferring the client-acceptable format with the highest q-value among
+/// the formats this server can encode. Falls back to PNG when nothing matches.
 fn determine_format(headers: &HeaderMap) -> OutputFormat {
     let accept = headers
         .get(header::ACCEPT)
         .and_then(|v| v.to_str().ok())
         .unwrap_or("");
 
-    if accept.contains("image/webp") {
-        OutputFormat::WebP
-    } else {
-        OutputFormat::Png
-    }
+    let accepted = parse_accept(accept);
+
+    // Ordered least-preferred-first so that, among equal q-values, `max_by`
+    // (which keeps the *last* maximum) picks our most preferred format.
+    let supported: [(&str, OutputFormat); 3] = [
+        ("image/png", OutputFormat::Png),
+        ("image/webp", OutputFormat::WebP),
+        ("image/avif", OutputFormat::Avif),
+    ];
+
+    supported
+        .iter()
+        .filter_map(|(mime, format)| {
+            accepted
+                .iter()
+                // `*/*` only ever stands in for our safe default (PNG) — a bare
+                // wildcard must never upgrade a client to WebP/AVIF it never
+                // explicitly named.
+                .find(|(accepted_mime, _)| accepted_mime == mime || (*accepted_mime == "*/*" && *mime == "image/png"))
+                .map(|(_, q)| (*format, *q))
+        })
+        // A q-value of 0 means "not acceptable" per RFC 7231
+        .filter(|(_, q)| *q > 0.0)
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(format, _)| format)
+        .unwrap_or(OutputFormat::Png)
 }
@@ -1,5 +1,5 @@
 use bytes::Bytes;
-use image::{DynamicImage, ImageOutputFormat, imageops::FilterType};
+use image::{codecs::avif::AvifEncoder, DynamicImage, ImageEncoder, ImageOutputFormat, imageops::FilterType};
 use std::io::Cursor;
 
 use crate::error::ConverterError;
@@ -9,6 +9,7 @@ pub enum OutputFormat {
     Png,
     #[default]
     WebP,
+    Avif,
 }
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -19,6 +20,25 @@ pub enum FitMode {
     Fill,
 }
 
+impl OutputFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "image/png",
+            OutputFormat::WebP => "image/webp",
+            OutputFormat::Avif => "image/avif",
+        }
+    }
+
+    /// File extension used for cache keys, without the leading dot.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Avif => "avif",
+        }
+    }
+}
+
 impl FitMode {
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
@@ -28,6 +48,14 @@ impl FitMode {
             _ => None,
         }
     }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FitMode::Cover => "cover",
+            FitMode::Contain => "contain",
+            FitMode::Fill => "fill",
+        }
+    }
 }
 
 pub struct ConversionRequest {
@@ -48,6 +76,62 @@ pub struct ConversionResult {
     pub output_height: u32,
 }
 
+/// Derives a deterministic cache key for a converted variant from the source
+/// key plus the normalized conversion parameters, e.g.
+/// `{key}-w{w}-h{h}-q{q}-{fit}.{ext}`.
+///
+/// Deliberately excludes the source object's ETag: if the source is replaced
+/// under the same key, the cached variant is served stale until the cache
+/// entry is evicted or overwritten out-of-band. Revalidation (`variant_etag`)
+/// only protects against a client's own stale copy, not a stale cache entry.
+pub fn cache_key(
+    key: &str,
+    width: Option<u32>,
+    height: Option<u32>,
+    quality: u8,
+    fit_mode: FitMode,
+    format: OutputFormat,
+) -> String {
+    let w = width.map(|v| v.to_string()).unwrap_or_else(|| "orig".to_string());
+    let h = height.map(|v| v.to_string()).unwrap_or_else(|| "orig".to_string());
+
+    format!(
+        "{}-w{}-h{}-q{}-{}.{}",
+        key,
+        w,
+        h,
+        quality,
+        fit_mode.as_str(),
+        format.extension()
+    )
+}
+
+/// Derives a deterministic, strong ETag for a converted variant from the
+/// source object's upstream ETag plus the normalized conversion parameters,
+/// so the ETag changes whenever either the source or the transform changes.
+pub fn variant_etag(
+    source_etag: Option<&str>,
+    width: Option<u32>,
+    height: Option<u32>,
+    quality: u8,
+    fit_mode: FitMode,
+    format: OutputFormat,
+) -> String {
+    let source = source_etag.unwrap_or("unknown").trim_matches('"');
+    let w = width.map(|v| v.to_string()).unwrap_or_else(|| "orig".to_string());
+    let h = height.map(|v| v.to_string()).unwrap_or_else(|| "orig".to_string());
+
+    format!(
+        "\"{}-w{}-h{}-q{}-{}.{}\"",
+        source,
+        w,
+        h,
+        quality,
+        fit_mode.as_str(),
+        format.extension()
+    )
+}
+
 pub fn convert(request: ConversionRequest) -> Result<ConversionResult, ConverterError> {
     // Load image from bytes
     let img = image::load_from_memory(&request.data)?;
@@ -107,17 +191,27 @@ fn encode_image(
     format: OutputFormat,
     quality: u8,
 ) -> Result<(Bytes, &'static str), ConverterError> {
-    match format {
+    let data = match format {
         OutputFormat::Png => {
             let mut buffer = Vec::new();
             img.write_to(&mut Cursor::new(&mut buffer), ImageOutputFormat::Png)?;
-            Ok((Bytes::from(buffer), "image/png"))
+            Bytes::from(buffer)
         }
         OutputFormat::WebP => {
             let rgba = img.to_rgba8();
             let encoder = webp::Encoder::from_rgba(&rgba, img.width(), img.height());
             let webp_data = encoder.encode(quality as f32);
-            Ok((Bytes::from(webp_data.to_vec()), "image/webp"))
+            Bytes::from(webp_data.to_vec())
         }
-    }
+        OutputFormat::Avif => {
+            let rgba = img.to_rgba8();
+            let mut buffer = Vec::new();
+            AvifEncoder::new_with_speed_quality(&mut buffer, 4, quality)
+                .write_image(&rgba, img.width(), img.height(), image::ColorType::Rgba8)
+                .map_err(ConverterError::ImageError)?;
+            Bytes::from(buffer)
+        }
+    };
+
+    Ok((data, format.content_type()))
 }
@@ -0,0 +1,298 @@
+use std::time::{Duration, Instant};
+
+use s3::creds::Credentials;
+use tokio::sync::RwLock;
+
+use crate::config::{Config, DEFAULT_DEV_ACCESS_KEY, DEFAULT_DEV_SECRET_KEY, DEFAULT_S3_ENDPOINT};
+use crate::error::ConverterError;
+
+const INSTANCE_METADATA_ENDPOINT: &str = "http://169.254.169.254/latest";
+/// STS web-identity tokens are typically valid for up to an hour; refresh
+/// well ahead of expiry rather than parsing the exact `Expiration` timestamp.
+const ASSUMED_ROLE_TTL: Duration = Duration::from_secs(15 * 60);
+/// Short timeout so hosts without IMDS/STS reachability (most non-EC2, non-
+/// IRSA environments) fail this provider fast instead of stalling the
+/// request path on the OS connect timeout.
+const METADATA_CLIENT_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn metadata_http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .connect_timeout(METADATA_CLIENT_TIMEOUT)
+        .timeout(METADATA_CLIENT_TIMEOUT)
+        .build()
+        .unwrap_or_else(|_| reqwest::Client::new())
+}
+
+/// Temporary (or static) credentials resolved from one of the providers in
+/// the chain, plus when they should be refreshed.
+#[derive(Clone)]
+struct ResolvedCredentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+    refresh_after: Option<Instant>,
+}
+
+impl ResolvedCredentials {
+    fn static_credentials(access_key: String, secret_key: String) -> Self {
+        Self {
+            access_key,
+            secret_key,
+            session_token: None,
+            refresh_after: None,
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.refresh_after.map(|at| Instant::now() >= at).unwrap_or(false)
+    }
+
+    fn to_s3_credentials(&self) -> Result<Credentials, ConverterError> {
+        Credentials::new(
+            Some(&self.access_key),
+            Some(&self.secret_key),
+            self.session_token.as_deref(),
+            None,
+            None,
+        )
+        .map_err(|e| ConverterError::S3Error(e.to_string()))
+    }
+}
+
+/// Resolves S3 credentials through a provider chain, caching the result
+/// until it is due for refresh:
+///
+/// 1. Explicit `S3_ACCESS_KEY` / `S3_SECRET_KEY`
+/// 2. Standard AWS env vars (`AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`, `AWS_SESSION_TOKEN`)
+/// 3. WebIdentity (`AWS_WEB_IDENTITY_TOKEN_FILE` + `AWS_ROLE_ARN`, exchanged via STS)
+/// 4. EC2/ECS instance metadata
+/// 5. The baked-in rustfs dev credentials, but only while `S3_ENDPOINT` is
+///    still the default local rustfs endpoint — so out-of-the-box `docker
+///    compose up` still works, without that fallback ever applying once a
+///    real endpoint is configured.
+pub struct CredentialProvider {
+    region: String,
+    s3_endpoint: String,
+    s3_access_key: Option<String>,
+    s3_secret_key: Option<String>,
+    cached: RwLock<Option<ResolvedCredentials>>,
+}
+
+impl CredentialProvider {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            region: config.s3_region.clone(),
+            s3_endpoint: config.s3_endpoint.clone(),
+            s3_access_key: config.s3_access_key.clone(),
+            s3_secret_key: config.s3_secret_key.clone(),
+            cached: RwLock::new(None),
+        }
+    }
+
+    pub async fn credentials(&self) -> Result<Credentials, ConverterError> {
+        {
+            let cached = self.cached.read().await;
+            if let Some(resolved) = cached.as_ref() {
+                if !resolved.is_expired() {
+                    return resolved.to_s3_credentials();
+                }
+            }
+        }
+
+        let resolved = self.resolve().await?;
+        let s3_credentials = resolved.to_s3_credentials();
+        *self.cached.write().await = Some(resolved);
+        s3_credentials
+    }
+
+    async fn resolve(&self) -> Result<ResolvedCredentials, ConverterError> {
+        if let Some(creds) = self.from_explicit_config() {
+            tracing::debug!("Using explicit S3_ACCESS_KEY/S3_SECRET_KEY credentials");
+            return Ok(creds);
+        }
+        if let Some(creds) = Self::from_aws_env() {
+            tracing::debug!("Using AWS environment variable credentials");
+            return Ok(creds);
+        }
+        match Self::from_web_identity(&self.region).await {
+            Ok(Some(creds)) => {
+                tracing::debug!("Using WebIdentity (AssumeRoleWithWebIdentity) credentials");
+                return Ok(creds);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("WebIdentity credential resolution failed: {}", e),
+        }
+        match Self::from_instance_metadata().await {
+            Ok(Some(creds)) => {
+                tracing::debug!("Using EC2/ECS instance metadata credentials");
+                return Ok(creds);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Instance metadata credential resolution failed: {}", e),
+        }
+        if let Some(creds) = self.from_rustfs_dev_default() {
+            tracing::debug!("Using baked-in rustfs dev credentials (default S3_ENDPOINT is in effect)");
+            return Ok(creds);
+        }
+
+        Err(ConverterError::S3Error(
+            "no S3 credentials available from config, environment, WebIdentity, or instance metadata".to_string(),
+        ))
+    }
+
+    fn from_explicit_config(&self) -> Option<ResolvedCredentials> {
+        Some(ResolvedCredentials::static_credentials(
+            self.s3_access_key.clone()?,
+            self.s3_secret_key.clone()?,
+        ))
+    }
+
+    /// Only applies while the default local rustfs endpoint is in effect, so
+    /// that pointing `S3_ENDPOINT` at a real deployment can never silently
+    /// fall through to these dev credentials.
+    fn from_rustfs_dev_default(&self) -> Option<ResolvedCredentials> {
+        if self.s3_endpoint != DEFAULT_S3_ENDPOINT {
+            return None;
+        }
+
+        Some(ResolvedCredentials::static_credentials(
+            DEFAULT_DEV_ACCESS_KEY.to_string(),
+            DEFAULT_DEV_SECRET_KEY.to_string(),
+        ))
+    }
+
+    fn from_aws_env() -> Option<ResolvedCredentials> {
+        let access_key = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+        let session_token = std::env::var("AWS_SESSION_TOKEN").ok();
+
+        Some(ResolvedCredentials {
+            access_key,
+            secret_key,
+            session_token,
+            refresh_after: None,
+        })
+    }
+
+    async fn from_web_identity(region: &str) -> Result<Option<ResolvedCredentials>, ConverterError> {
+        let (token_file, role_arn) = match (
+            std::env::var("AWS_WEB_IDENTITY_TOKEN_FILE").ok(),
+            std::env::var("AWS_ROLE_ARN").ok(),
+        ) {
+            (Some(token_file), Some(role_arn)) => (token_file, role_arn),
+            _ => return Ok(None),
+        };
+
+        let token = std::fs::read_to_string(&token_file)
+            .map_err(|e| ConverterError::S3Error(format!("failed to read web identity token: {}", e)))?;
+        let token = token.trim();
+
+        let sts_endpoint = format!("https://sts.{}.amazonaws.com/", region);
+        let session_name = "soeji-converter";
+
+        let client = metadata_http_client();
+        let response = client
+            .get(&sts_endpoint)
+            .query(&[
+                ("Action", "AssumeRoleWithWebIdentity"),
+                ("Version", "2011-06-15"),
+                ("RoleArn", &role_arn),
+                ("RoleSessionName", session_name),
+                ("WebIdentityToken", token),
+            ])
+            .send()
+            .await
+            .map_err(|e| ConverterError::S3Error(format!("STS request failed: {}", e)))?
+            .error_for_status()
+            .map_err(|e| ConverterError::S3Error(format!("STS request failed: {}", e)))?
+            .text()
+            .await
+            .map_err(|e| ConverterError::S3Error(format!("failed to read STS response: {}", e)))?;
+
+        let access_key = extract_xml_tag(&response, "AccessKeyId")
+            .ok_or_else(|| ConverterError::S3Error("STS response missing AccessKeyId".to_string()))?;
+        let secret_key = extract_xml_tag(&response, "SecretAccessKey")
+            .ok_or_else(|| ConverterError::S3Error("STS response missing SecretAccessKey".to_string()))?;
+        let session_token = extract_xml_tag(&response, "SessionToken");
+
+        Ok(Some(ResolvedCredentials {
+            access_key,
+            secret_key,
+            session_token,
+            refresh_after: Some(Instant::now() + ASSUMED_ROLE_TTL),
+        }))
+    }
+
+    async fn from_instance_metadata() -> Result<Option<ResolvedCredentials>, ConverterError> {
+        let client = metadata_http_client();
+
+        // IMDSv2: fetch a session token first
+        let token = client
+            .put(format!("{}/api/token", INSTANCE_METADATA_ENDPOINT))
+            .header("X-aws-ec2-metadata-token-ttl-seconds", "21600")
+            .send()
+            .await;
+
+        let token = match token {
+            Ok(resp) if resp.status().is_success() => resp.text().await.ok(),
+            _ => None,
+        };
+
+        let mut request = client.get(format!(
+            "{}/meta-data/iam/security-credentials/",
+            INSTANCE_METADATA_ENDPOINT
+        ));
+        if let Some(token) = &token {
+            request = request.header("X-aws-ec2-metadata-token", token);
+        }
+
+        let role_name = match request.send().await {
+            Ok(resp) if resp.status().is_success() => resp.text().await.unwrap_or_default(),
+            _ => return Ok(None),
+        };
+        let role_name = role_name.trim();
+        if role_name.is_empty() {
+            return Ok(None);
+        }
+
+        let mut request = client.get(format!(
+            "{}/meta-data/iam/security-credentials/{}",
+            INSTANCE_METADATA_ENDPOINT, role_name
+        ));
+        if let Some(token) = &token {
+            request = request.header("X-aws-ec2-metadata-token", token);
+        }
+
+        let body: serde_json::Value = request
+            .send()
+            .await
+            .map_err(|e| ConverterError::S3Error(format!("instance metadata request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| ConverterError::S3Error(format!("failed to parse instance metadata response: {}", e)))?;
+
+        let access_key = body["AccessKeyId"].as_str().map(str::to_string);
+        let secret_key = body["SecretAccessKey"].as_str().map(str::to_string);
+        let session_token = body["Token"].as_str().map(str::to_string);
+
+        match (access_key, secret_key) {
+            (Some(access_key), Some(secret_key)) => Ok(Some(ResolvedCredentials {
+                access_key,
+                secret_key,
+                session_token,
+                refresh_after: Some(Instant::now() + ASSUMED_ROLE_TTL),
+            })),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Minimal extraction of `<Tag>value</Tag>` from an STS XML response, avoiding
+/// a full XML parser for this one-shot, well-known response shape.
+fn extract_xml_tag(xml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = xml.find(&open)? + open.len();
+    let end = xml[start..].find(&close)? + start;
+    Some(xml[start..end].to_string())
+}
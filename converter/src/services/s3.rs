@@ -1,41 +1,219 @@
-use bytes::Bytes;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, StreamExt};
 use s3::bucket::Bucket;
-use s3::creds::Credentials;
 use s3::Region;
 
 use crate::config::Config;
 use crate::error::ConverterError;
+use crate::services::credentials::CredentialProvider;
 
 #[derive(Clone)]
 pub struct S3Client {
-    credentials: Credentials,
+    credential_provider: Arc<CredentialProvider>,
     region: Region,
+    parallel_download_threshold: u64,
+    parallel_download_part_size: u64,
+    parallel_download_max_concurrency: usize,
+}
+
+/// Prefix S3 uses for user-defined object metadata, both on request (when
+/// writing) and in the response headers (when reading) echoed back.
+const USER_METADATA_PREFIX: &str = "x-amz-meta-";
+
+/// An object fetched from S3 together with the metadata needed for
+/// conditional-request (ETag / Last-Modified) support.
+pub struct S3Object {
+    pub data: Bytes,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// User-defined metadata (`x-amz-meta-*`), keyed without the prefix.
+    pub metadata: std::collections::HashMap<String, String>,
+}
+
+fn find_header(headers: &std::collections::HashMap<String, String>, name: &str) -> Option<String> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.clone())
+}
+
+fn find_user_metadata(headers: &std::collections::HashMap<String, String>) -> std::collections::HashMap<String, String> {
+    headers
+        .iter()
+        .filter_map(|(k, v)| {
+            k.to_lowercase()
+                .strip_prefix(USER_METADATA_PREFIX)
+                .map(|stripped| (stripped.to_string(), v.clone()))
+        })
+        .collect()
+}
+
+/// Parses the object's total size out of a ranged response's `Content-Range:
+/// bytes {start}-{end}/{total}` header.
+fn parse_content_range_total(headers: &std::collections::HashMap<String, String>) -> Option<u64> {
+    let content_range = find_header(headers, "content-range")?;
+    let total = content_range.rsplit('/').next()?;
+    total.parse().ok()
 }
 
 impl S3Client {
     pub fn new(config: &Config) -> Result<Self, ConverterError> {
-        let credentials = Credentials::new(
-            Some(&config.s3_access_key),
-            Some(&config.s3_secret_key),
-            None,
-            None,
-            None,
-        )
-        .map_err(|e| ConverterError::S3Error(e.to_string()))?;
-
         let region = Region::Custom {
             region: config.s3_region.clone(),
             endpoint: config.s3_endpoint.clone(),
         };
 
-        Ok(Self { credentials, region })
+        Ok(Self {
+            credential_provider: Arc::new(CredentialProvider::new(config)),
+            region,
+            parallel_download_threshold: config.parallel_download_threshold,
+            parallel_download_part_size: config.parallel_download_part_size,
+            parallel_download_max_concurrency: config.parallel_download_max_concurrency,
+        })
     }
 
-    pub async fn get_object(&self, bucket_name: &str, key: &str) -> Result<Bytes, ConverterError> {
-        let bucket = Bucket::new(bucket_name, self.region.clone(), self.credentials.clone())
+    /// Fetches an object, transparently using concurrent ranged downloads for
+    /// large objects (see `get_object_ranged`). Size is learned from a single
+    /// ranged GET of the first part rather than a separate HEAD request, so
+    /// small objects (the common case) still cost one round-trip.
+    pub async fn get_object(&self, bucket_name: &str, key: &str) -> Result<S3Object, ConverterError> {
+        self.get_object_impl(bucket_name, key, true).await
+    }
+
+    /// Fetches an object without the parallel-download size probe. Use this
+    /// for lookups that are never worth parallelizing, such as cache-bucket
+    /// variant reads, so they stay a single plain `get_object` call.
+    pub async fn get_object_uncond(&self, bucket_name: &str, key: &str) -> Result<S3Object, ConverterError> {
+        self.get_object_impl(bucket_name, key, false).await
+    }
+
+    async fn get_object_impl(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        allow_parallel: bool,
+    ) -> Result<S3Object, ConverterError> {
+        let credentials = self.credential_provider.credentials().await?;
+        let bucket = Bucket::new(bucket_name, self.region.clone(), credentials)
             .map_err(|e| ConverterError::S3Error(e.to_string()))?
             .with_path_style();
 
+        if allow_parallel {
+            // Probe with a ranged GET for the first part instead of a standalone
+            // HEAD request, so the common small-object case still costs a single
+            // round-trip (the probe response *is* the full object in that case).
+            let part_size = self.parallel_download_part_size.max(1);
+            match bucket.get_object_range(key, 0, Some(part_size - 1)).await {
+                Ok(probe) if probe.status_code() == 404 => {
+                    return Err(ConverterError::NotFound(format!("{}/{}", bucket_name, key)));
+                }
+                Ok(probe) if probe.status_code() >= 300 => {
+                    // Any other status of 300 or above (403, 416, 500, ...):
+                    // skip the parallel path and fall through to a plain
+                    // get_object call below, rather than treating an error
+                    // response body as if it were object data.
+                    tracing::debug!(
+                        "Ranged GET probe for {}/{} returned status {}, falling back to get_object",
+                        bucket_name,
+                        key,
+                        probe.status_code()
+                    );
+                }
+                Ok(probe) if probe.status_code() == 200 => {
+                    // 200 (rather than 206 Partial Content) means the backend
+                    // ignored the Range request and returned the whole object.
+                    // Treat the probe body as complete rather than assuming it's
+                    // only the first part_size bytes -- never attempt the
+                    // remaining-range requests a non-Range-aware backend can't
+                    // satisfy correctly.
+                    let probe_headers = probe.headers();
+                    return Ok(S3Object {
+                        etag: find_header(&probe_headers, "etag"),
+                        last_modified: find_header(&probe_headers, "last-modified"),
+                        metadata: find_user_metadata(&probe_headers),
+                        data: Bytes::from(probe.to_vec()),
+                    });
+                }
+                Ok(probe) => {
+                    let probe_headers = probe.headers();
+                    let probe_bytes = probe.to_vec();
+
+                    // A 206 response without a parseable Content-Range total
+                    // can't be trusted to tell us the real object size --
+                    // don't guess, just fall through to a plain get_object
+                    // below rather than risk truncating a larger object.
+                    if let Some(total_len) = parse_content_range_total(&probe_headers) {
+                        let etag = find_header(&probe_headers, "etag");
+                        let last_modified = find_header(&probe_headers, "last-modified");
+                        let metadata = find_user_metadata(&probe_headers);
+
+                        if total_len <= part_size {
+                            return Ok(S3Object {
+                                data: Bytes::from(probe_bytes),
+                                etag,
+                                last_modified,
+                                metadata,
+                            });
+                        }
+
+                        if total_len >= self.parallel_download_threshold {
+                            tracing::debug!(
+                                "Downloading {}/{} ({} bytes) as concurrent ranged parts",
+                                bucket_name,
+                                key,
+                                total_len
+                            );
+                            // Reuse the already-fetched first part instead of
+                            // re-requesting byte range 0..part_size.
+                            return self
+                                .get_object_ranged(&bucket, bucket_name, key, total_len, Bytes::from(probe_bytes), etag, last_modified, metadata)
+                                .await;
+                        }
+
+                        // Bigger than one part but below the parallel
+                        // threshold: one more sequential ranged call for the
+                        // remainder, rather than paying for concurrency that
+                        // isn't worth it at this size.
+                        let rest = bucket
+                            .get_object_range(key, part_size, Some(total_len - 1))
+                            .await
+                            .map_err(|e| {
+                                let err_str = e.to_string();
+                                if err_str.contains("NoSuchKey") || err_str.contains("404") || err_str.contains("not found") {
+                                    ConverterError::NotFound(format!("{}/{}", bucket_name, key))
+                                } else {
+                                    ConverterError::S3Error(err_str)
+                                }
+                            })?;
+
+                        let mut data = BytesMut::with_capacity(total_len as usize);
+                        data.extend_from_slice(&probe_bytes);
+                        data.extend_from_slice(&rest.to_vec());
+
+                        return Ok(S3Object {
+                            data: data.freeze(),
+                            etag,
+                            last_modified,
+                            metadata,
+                        });
+                    }
+
+                    tracing::debug!(
+                        "Ranged GET probe for {}/{} returned 206 without a parseable Content-Range, falling back to get_object",
+                        bucket_name,
+                        key
+                    );
+                }
+                Err(e) => {
+                    // Range requests may not be supported by every S3-compatible
+                    // target; fall back to a plain get_object below.
+                    tracing::debug!("Ranged GET probe for {}/{} failed ({}), falling back to get_object", bucket_name, key, e);
+                }
+            }
+        }
+
         let response = bucket
             .get_object(key)
             .await
@@ -52,6 +230,103 @@ impl S3Client {
             return Err(ConverterError::NotFound(format!("{}/{}", bucket_name, key)));
         }
 
-        Ok(Bytes::from(response.to_vec()))
+        let headers = response.headers();
+        let etag = find_header(&headers, "etag");
+        let last_modified = find_header(&headers, "last-modified");
+        let metadata = find_user_metadata(&headers);
+
+        Ok(S3Object {
+            data: Bytes::from(response.to_vec()),
+            etag,
+            last_modified,
+            metadata,
+        })
+    }
+
+    /// Fetches a large object as fixed-size byte ranges, downloaded
+    /// concurrently (bounded by `parallel_download_max_concurrency`) and
+    /// reassembled in order. `first_part` is the already-fetched `0..part_size`
+    /// range from the caller's size probe, along with its headers, so this
+    /// only needs to request the *remaining* ranges rather than re-fetching
+    /// the first one.
+    #[allow(clippy::too_many_arguments)]
+    async fn get_object_ranged(
+        &self,
+        bucket: &Bucket,
+        bucket_name: &str,
+        key: &str,
+        content_length: u64,
+        first_part: Bytes,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        metadata: std::collections::HashMap<String, String>,
+    ) -> Result<S3Object, ConverterError> {
+        let part_size = self.parallel_download_part_size.max(1);
+        let mut data = BytesMut::with_capacity(content_length as usize);
+        data.extend_from_slice(&first_part);
+
+        let ranges: Vec<(u64, u64)> = (part_size..content_length)
+            .step_by(part_size as usize)
+            .map(|start| (start, (start + part_size - 1).min(content_length - 1)))
+            .collect();
+
+        let parts = stream::iter(ranges.into_iter().map(|(start, end)| {
+            let bucket = bucket.clone();
+            async move {
+                bucket
+                    .get_object_range(key, start, Some(end))
+                    .await
+                    .map_err(|e| ConverterError::S3Error(e.to_string()))
+            }
+        }))
+        .buffered(self.parallel_download_max_concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+
+        for part in parts {
+            let response = part.map_err(|e| {
+                let err_str = e.to_string();
+                if err_str.contains("NoSuchKey") || err_str.contains("404") || err_str.contains("not found") {
+                    ConverterError::NotFound(format!("{}/{}", bucket_name, key))
+                } else {
+                    e
+                }
+            })?;
+
+            data.extend_from_slice(&response.to_vec());
+        }
+
+        Ok(S3Object {
+            data: data.freeze(),
+            etag,
+            last_modified,
+            metadata,
+        })
+    }
+
+    /// Writes an object, optionally attaching user-defined metadata
+    /// (stored as `x-amz-meta-*` headers and echoed back on later reads).
+    pub async fn put_object(
+        &self,
+        bucket_name: &str,
+        key: &str,
+        data: &[u8],
+        metadata: &[(&str, &str)],
+    ) -> Result<(), ConverterError> {
+        let credentials = self.credential_provider.credentials().await?;
+        let mut bucket = Bucket::new(bucket_name, self.region.clone(), credentials)
+            .map_err(|e| ConverterError::S3Error(e.to_string()))?
+            .with_path_style();
+
+        for (name, value) in metadata {
+            bucket.add_header(&format!("{}{}", USER_METADATA_PREFIX, name), value);
+        }
+
+        bucket
+            .put_object(key, data)
+            .await
+            .map_err(|e| ConverterError::S3Error(e.to_string()))?;
+
+        Ok(())
     }
 }